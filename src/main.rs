@@ -8,13 +8,19 @@ use std::convert::TryFrom;
 use std::io;
 use std::io::{Bytes, Read, Stdin, Stdout, Write};
 use std::string::FromUtf8Error;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
 /// Custom error type alias.
 type Result<T, E = Error> = std::result::Result<T, E>;
 
 /// Represent a git branch with commit attributes.
-struct Branch<'repo> {
+///
+/// Holds only owned data (no borrowed `git2::Branch`) so instances can be sent
+/// between worker threads; mutating operations take a `&Repository` handle and
+/// re-resolve the ref by name on the calling thread.
+struct Branch {
     /// Commit id.
     id: Oid,
     name: String,
@@ -24,17 +30,60 @@ struct Branch<'repo> {
     /// Commit message of the last commit to the branch.
     commit_summary: String,
     branch_type: BranchType,
-    // branch_type: &'repo str,
     commit_time: NaiveDateTime,
+    /// Commits this branch is ahead of its tracking branch, `None` if no upstream.
+    ahead: Option<usize>,
+    /// Commits this branch is behind its tracking branch, `None` if no upstream.
+    behind: Option<usize>,
+    /// Whether this branch's tip is fully contained in the default branch.
+    is_merged: bool,
     is_head: bool,
-    branch: git2::Branch<'repo>,
 }
 
-impl<'repo> Branch<'repo> {
+impl Branch {
     // Result<()> is short for Result<(), Error>
-    fn delete(&mut self) -> Result<()> {
-        self.branch.delete().map_err(From::from) // the fn says we return Error but delete return git2 error
-                                                 // same as Ok(self.branch.delete()?)
+    fn delete(&mut self, repo: &Repository) -> Result<()> {
+        repo.find_branch(&self.name, self.branch_type)?
+            .delete()
+            .map_err(From::from)
+    }
+
+    /// Delete a remote branch by pushing an empty-source delete refspec.
+    ///
+    /// `name` is a remote tracking name like `origin/feature`; it is split into
+    /// its `<remote>/<branch>` parts, the remote is opened and we push
+    /// `:refs/heads/<branch>`. Credentials are resolved through an SSH-agent,
+    /// then a configured credential helper, then anonymous access.
+    fn delete_remote(&mut self, repo: &Repository) -> Result<()> {
+        let mut parts = self.name.splitn(2, '/');
+        let remote_name = parts.next().unwrap_or_default();
+        let branch_name = parts.next().unwrap_or_default();
+
+        let mut remote = repo.find_remote(remote_name)?;
+
+        let mut callbacks = git2::RemoteCallbacks::new();
+        callbacks.credentials(|url, username, _allowed| {
+            let username = username.unwrap_or("git");
+            git2::Cred::ssh_key_from_agent(username)
+                .or_else(|_| git2::Cred::credential_helper(&repo.config()?, url, Some(username)))
+                .or_else(|_| git2::Cred::default())
+        });
+
+        let mut push_options = git2::PushOptions::new();
+        push_options.remote_callbacks(callbacks);
+
+        let refspec = format!(":refs/heads/{}", branch_name);
+        remote.push(&[refspec.as_str()], Some(&mut push_options))?;
+        Ok(())
+    }
+
+    /// Rename the branch (non-force) and update the stored name so later
+    /// actions in the loop operate on the renamed ref.
+    fn rename(&mut self, repo: &Repository, new_name: &str) -> Result<()> {
+        repo.find_branch(&self.name, self.branch_type)?
+            .rename(new_name, false)?;
+        self.name = new_name.to_owned();
+        Ok(())
     }
 }
 
@@ -53,6 +102,9 @@ enum Error {
     #[error(transparent)]
     FromUtf8Error(#[from] FromUtf8Error),
 
+    #[error(transparent)]
+    ParseIntError(#[from] std::num::ParseIntError),
+
     #[error("Invalid input, dont know '{0}'")]
     InvalidInput(char),
 }
@@ -61,6 +113,8 @@ enum Error {
 enum BranchAction {
     Keep,
     Delete,
+    DeleteAllMerged,
+    Rename,
     Quit,
 }
 
@@ -71,6 +125,8 @@ impl TryFrom<char> for BranchAction {
         match value {
             'k' => Ok(BranchAction::Keep),
             'd' => Ok(BranchAction::Delete),
+            'm' => Ok(BranchAction::DeleteAllMerged),
+            'r' => Ok(BranchAction::Rename),
             'q' => Ok(BranchAction::Quit),
             _ => Err(Error::InvalidInput(value)),
         }
@@ -98,6 +154,8 @@ fn process_user_request(
         write!(stdout, "select from the following:\r\n")?;
         write!(stdout, "\tk - Keep the branch\r\n")?;
         write!(stdout, "\td - Delete the branch\r\n")?;
+        write!(stdout, "\tm - Delete all remaining merged local branches\r\n")?;
+        write!(stdout, "\tr - Rename the branch\r\n")?;
         write!(stdout, "\tq - Quit\r\n")?;
         write!(stdout, "\t? - Help\r\n")?;
         stdout.flush()?;
@@ -125,6 +183,11 @@ fn request_user_input(
         branch.name,
     )?;
 
+    if branch.is_merged {
+        set_color(Color::Magenta)?;
+        write!(stdout, " [merged]")?;
+    }
+
     write!(
         stdout,
         "\n\r\tlast commit as {}\n\r\tlast commit id: {} \n\r\tcommit author: {} \n\r\tcommit summary: {}\n\r",
@@ -134,8 +197,17 @@ fn request_user_input(
         branch.commit_summary,
     )?;
 
+    match (branch.ahead, branch.behind) {
+        (Some(ahead), Some(behind)) => write!(
+            stdout,
+            "\r\tupstream: {} ahead, {} behind\n\r",
+            ahead, behind
+        )?,
+        _ => write!(stdout, "\r\tupstream: no upstream\n\r")?,
+    }
+
     set_color(Color::Blue)?;
-    write!(stdout, "(k/d/q/?) > ",)?;
+    write!(stdout, "(k/d/m/r/q/?) > ",)?;
 
     stdout.flush()?;
 
@@ -155,68 +227,207 @@ fn branch_type_to_str(branch_type: git2::BranchType) -> &'static str {
     }
 }
 
+/// Resolve the heavy per-branch metadata (commit author/summary/time,
+/// ahead/behind, merged flag) for a single branch. Takes its own `repo`
+/// handle so it can run on a worker thread.
+fn resolve_branch(
+    repo: &Repository,
+    name: String,
+    branch_type: BranchType,
+    is_head: bool,
+    default_oid: Option<Oid>,
+) -> Result<Branch> {
+    let git_branch = repo.find_branch(&name, branch_type)?;
+    let commit = git_branch.get().peel_to_commit()?;
+
+    let commit_time = commit.time();
+    let commit_author = commit.author().name().unwrap_or("no author").to_owned();
+    let commit_summary = commit.summary().unwrap_or("no summary").to_owned();
+    let offset = Duration::minutes(i64::from(commit_time.offset_minutes()));
+    let commit_time = NaiveDateTime::from_timestamp(commit_time.seconds(), 0) + offset;
+
+    // Diverge counts against the tracking branch, if one is configured.
+    let (ahead, behind) = match git_branch.upstream() {
+        Ok(upstream) => {
+            let upstream_oid = upstream.get().peel_to_commit()?.id();
+            let (ahead, behind) = repo.graph_ahead_behind(commit.id(), upstream_oid)?;
+            (Some(ahead), Some(behind))
+        }
+        Err(_) => (None, None),
+    };
+
+    // A branch is merged when its tip is the default-branch tip or an ancestor of it.
+    let is_merged = match default_oid {
+        Some(target_oid) => {
+            target_oid == commit.id() || repo.graph_descendant_of(target_oid, commit.id())?
+        }
+        None => false,
+    };
+
+    Ok(Branch {
+        id: commit.id(),
+        commit_author,
+        commit_summary,
+        commit_time,
+        ahead,
+        behind,
+        is_merged,
+        branch_type,
+        name,
+        is_head,
+    })
+}
+
 /// Interact with git branches.
-fn get_branches<'a>(
-    repo: &'a Repository,
+///
+/// Metadata collection runs in two passes: a cheap single pass on the main
+/// handle that filters and collects `(name, branch_type, is_head)` tuples,
+/// then a worker pool that resolves the expensive per-branch data. Because
+/// `git2::Repository` isn't `Sync`, each worker opens its own handle and pulls
+/// work off a shared channel.
+fn get_branches(
+    repo: &Repository,
     ignore: &HashSet<String>,
     filter_in: Option<&str>,
     local_only: &bool,
-) -> Result<Vec<Branch<'a>>> {
-
+) -> Result<Vec<Branch>> {
     let local_only = local_only.then(|| BranchType::Local);
 
-    let mut branches = repo
-        .branches(local_only)?
-        .map(|branch| {
-            let (branch, branch_type) = branch?;
-            let name = String::from_utf8(branch.name_bytes()?.to_vec())?;
-            let commit = branch.get().peel_to_commit()?;
-
-            let commit_time = commit.time();
-            let commit_author = commit.author().name().unwrap_or("no author").to_owned();
-            let commit_summary = commit.summary().unwrap_or("no summary").to_owned();
-            let offset = Duration::minutes(i64::from(commit_time.offset_minutes()));
-
-            let commit_time = NaiveDateTime::from_timestamp(commit_time.seconds(), 0) + offset;
-            Ok(Branch {
-                id: commit.id(),
-                commit_author,
-                commit_summary,
-                commit_time,
-                branch_type,
-                name,
-                is_head: branch.is_head(),
-                branch,
-            })
-        })
-        .filter(|branch| {
-            if let Ok(branch) = branch {
-                if filter_in.is_some() {
-                    let fo: &str = &*filter_in.unwrap().to_lowercase(); //convert String to &str
-
-                    let bn_lower: &str = &*branch.name.to_lowercase();
-
-                    !ignore.contains(&branch.name) && bn_lower.contains(fo)
-                } else {
-                    !ignore.contains(&branch.name)
+    // Tip of the default branch, used to flag fully-merged branches.
+    let default_oid = ["main", "master", "default"]
+        .iter()
+        .find_map(|name| repo.find_branch(name, BranchType::Local).ok())
+        .and_then(|branch| branch.get().peel_to_commit().ok())
+        .map(|commit| commit.id());
+
+    // Pass one: lightweight tuples and name-based filtering on the main handle.
+    let filter_in = filter_in.map(|f| f.to_lowercase());
+    let mut work: Vec<(String, BranchType, bool)> = Vec::new();
+    for branch in repo.branches(local_only)? {
+        let (branch, branch_type) = branch?;
+        let name = String::from_utf8(branch.name_bytes()?.to_vec())?;
+
+        if ignore.contains(&name) {
+            continue;
+        }
+        if let Some(filter) = &filter_in {
+            if !name.to_lowercase().contains(filter.as_str()) {
+                continue;
+            }
+        }
+
+        work.push((name, branch_type, branch.is_head()));
+    }
+
+    // Pass two: resolve the heavy data across a pool of worker threads, each
+    // with an independent `Repository` handle fed from a shared channel.
+    let worker_count = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .min(work.len().max(1));
+
+    let (work_tx, work_rx) = mpsc::channel::<(String, BranchType, bool)>();
+    for item in work {
+        work_tx.send(item).ok();
+    }
+    drop(work_tx);
+    let work_rx = Arc::new(Mutex::new(work_rx));
+
+    let (res_tx, res_rx) = mpsc::channel::<Result<Branch>>();
+    let repo_path = repo.path().to_path_buf();
+
+    let mut handles = Vec::with_capacity(worker_count);
+    for _ in 0..worker_count {
+        let work_rx = Arc::clone(&work_rx);
+        let res_tx = res_tx.clone();
+        let repo_path = repo_path.clone();
+        handles.push(std::thread::spawn(move || {
+            let repo = match Repository::open(&repo_path) {
+                Ok(repo) => repo,
+                Err(error) => {
+                    res_tx.send(Err(error.into())).ok();
+                    return;
                 }
-            } else {
-                true
+            };
+            loop {
+                let item = {
+                    let rx = work_rx.lock().unwrap();
+                    rx.recv()
+                };
+                let (name, branch_type, is_head) = match item {
+                    Ok(item) => item,
+                    Err(_) => break,
+                };
+                res_tx
+                    .send(resolve_branch(&repo, name, branch_type, is_head, default_oid))
+                    .ok();
             }
-        })
-        .collect::<Result<Vec<_>>>()?;
+        }));
+    }
+    drop(res_tx);
+
+    let mut branches = Vec::new();
+    for res in res_rx {
+        branches.push(res?);
+    }
+    for handle in handles {
+        handle.join().ok();
+    }
 
     branches.sort_unstable_by_key(|branch| branch.commit_time);
 
     Ok(branches)
 }
 
+/// Non-interactively delete local branches whose last commit is older than
+/// `days` days, emitting one line per branch including the restore command.
+/// With `dry_run` set the branches are reported but left untouched.
+fn prune_older_than(
+    repo: &Repository,
+    branches: &mut [Branch],
+    days: i64,
+    dry_run: bool,
+) -> Result<()> {
+    let cutoff = Local::now().naive_local() - Duration::days(days);
+    let mut stdout = io::stdout();
+
+    for branch in branches.iter_mut() {
+        if branch.is_head || branch.branch_type != BranchType::Local {
+            continue;
+        }
+        if branch.commit_time >= cutoff {
+            continue;
+        }
+
+        if dry_run {
+            writeln!(
+                stdout,
+                "would delete '{}' (last commit {})",
+                branch.name, branch.commit_time
+            )?;
+        } else {
+            branch.delete(repo)?;
+            writeln!(
+                stdout,
+                "deleted '{}'. to undo, run: git branch {} {}",
+                branch.name, branch.name, branch.id
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
 fn main() {
     let yaml = load_yaml!("../cli.yaml");
     let matches = App::from(yaml).get_matches();
 
     let filter_in = matches.value_of("filter_in");
     let local_only = matches.is_present("local_only");
+    let allow_remote_delete = matches.is_present("allow_remote_delete");
+    let prune_older_than_days = matches.value_of("prune_older_than");
+    let assume_yes = matches.is_present("yes");
+    let dry_run = matches.is_present("dry_run");
 
     let indelible_branches: HashSet<String> = vec![
         String::from("origin/main"),
@@ -233,6 +444,21 @@ fn main() {
     let result = (|| -> Result<_> {
         let repo = Repository::open_from_env()?;
 
+        // Non-interactive pruning bypasses the raw-mode stdin loop entirely so
+        // the tool can run in cleanup jobs and CI.
+        if let Some(days) = prune_older_than_days {
+            let days: i64 = days.parse()?;
+            let branches = &mut get_branches(&repo, &indelible_branches, filter_in, &local_only)?;
+
+            if !assume_yes && !dry_run {
+                eprintln!("refusing to prune without --yes (or use --dry-run)");
+                return Ok(());
+            }
+
+            prune_older_than(&repo, branches, days, dry_run)?;
+            return Ok(());
+        }
+
         crossterm::terminal::enable_raw_mode()?;
 
         let mut stdout = io::stdout();
@@ -267,40 +493,89 @@ fn main() {
                     .count(),
             )?;
 
-            for branch in branches {
-                //write!(stdout, "author: {}", branch.author).expect("no author");
-                //write!(stdout, "author: {}", branch.author).unwrap();
-                //write!(stdout, "author: {}", branch.commit_author)?;
-                //write!(stdout, "summary: {}", branch.commit_summary)?;
-
-                if branch.is_head {
+            let mut i = 0;
+            'outer: while i < branches.len() {
+                if branches[i].is_head {
                     set_color(Color::Yellow)?;
-                    write!(stdout, "Ignoring current branch: '{}'\r\n", branch.name)?
-                } else {
-                    match request_user_input(&mut stdout, &mut stdin, &branch)? {
-                        BranchAction::Quit => return Ok(()),
-                        BranchAction::Keep => {
-                            write!(stdout, "")?;
+                    write!(stdout, "Ignoring current branch: '{}'\r\n", branches[i].name)?;
+                    i += 1;
+                    continue;
+                }
+
+                match request_user_input(&mut stdout, &mut stdin, &branches[i])? {
+                    BranchAction::Quit => return Ok(()),
+                    BranchAction::Keep => {
+                        write!(stdout, "")?;
+                    }
+                    BranchAction::Delete => {
+                        let branch = &mut branches[i];
+                        if branch.branch_type == BranchType::Local {
+                            branch.delete(&repo)?;
+                            set_color(Color::Red)?;
+                            write!(stdout, "'{}' was deleted.\r\n ", branch.name)?;
+
+                            set_color(Color::White)?;
+                            write!(
+                                stdout,
+                                "to undo, run:\r\n \tgit branch {} {}\r\n\n",
+                                branch.name, branch.id
+                            )?
+                        } else if allow_remote_delete {
+                            branch.delete_remote(&repo)?;
+                            set_color(Color::Red)?;
+                            write!(stdout, "'{}' was deleted from its remote.\r\n ", branch.name)?;
+                        } else {
+                            set_color(Color::Red)?;
+                            write!(stdout, "\tI don't want to be responsible for deleting remote branches. \n\r\tgithub.com has a great interface for such endeavours.\r\n\tpass --allow-remote-delete to override.\r\n")?;
+                        }
+                    }
+                    BranchAction::DeleteAllMerged => {
+                        for j in i..branches.len() {
+                            let branch = &mut branches[j];
+                            if branch.is_head
+                                || !branch.is_merged
+                                || branch.branch_type != BranchType::Local
+                            {
+                                continue;
+                            }
+                            branch.delete(&repo)?;
+                            set_color(Color::Red)?;
+                            write!(stdout, "'{}' was deleted.\r\n ", branch.name)?;
+
+                            set_color(Color::White)?;
+                            write!(
+                                stdout,
+                                "to undo, run:\r\n \tgit branch {} {}\r\n\n",
+                                branch.name, branch.id
+                            )?;
                         }
-                        BranchAction::Delete => {
-                            if branch.branch_type == BranchType::Local {
-                                branch.delete()?;
-                                set_color(Color::Red)?;
-                                write!(stdout, "'{}' was deleted.\r\n ", branch.name)?;
-
-                                set_color(Color::White)?;
-                                write!(
-                                    stdout,
-                                    "to undo, run:\r\n \tgit branch {} {}\r\n\n",
-                                    branch.name, branch.id
-                                )?
-                            } else {
-                                set_color(Color::Red)?;
-                                write!(stdout, "\tI don't want to be responsible for deleting remote branches. \n\r\tgithub.com has a great interface for such endeavours.\r\n")?;
+                        break 'outer;
+                    }
+                    BranchAction::Rename => {
+                        set_color(Color::Blue)?;
+                        write!(stdout, "new name > ")?;
+                        stdout.flush()?;
+
+                        let mut buf = Vec::new();
+                        for byte in stdin.by_ref() {
+                            let byte = byte?;
+                            if byte == b'\n' || byte == b'\r' {
+                                break;
                             }
+                            buf.push(byte);
                         }
+                        let new_name = String::from_utf8(buf)?;
+                        write!(stdout, "\r\n")?;
+
+                        let branch = &mut branches[i];
+                        branch.rename(&repo, &new_name)?;
+
+                        set_color(Color::Green)?;
+                        write!(stdout, "renamed to '{}'\r\n", branch.name)?;
                     }
                 }
+
+                i += 1;
             }
         }
 